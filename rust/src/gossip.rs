@@ -0,0 +1,297 @@
+use crate::tree::Tree;
+use moka::sync::Cache;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/*
+Gossip sharing of the approximate radix tree across router replicas.
+
+Each replica owns a node id and a UDP socket. Every `tree.insert` in `dispatch`
+also enqueues a compact delta -- only the (truncated) prefix, the worker url, a
+monotonic timestamp, and a per-sender sequence number travel on the wire, so
+packets stay bounded. A broadcaster task batches the outbound buffer to the
+configured peers on an interval; a receiver task applies incoming deltas to the
+local tree with last-writer-wins on the timestamp and the same LRU eviction,
+deduplicating by sequence number to tolerate UDP reordering and loss.
+*/
+
+// Cap the prefix carried on the wire so a single batch fits in a UDP datagram.
+const MAX_PREFIX_CHARS: usize = 128;
+// Flush at most this many deltas per broadcast batch (one UDP datagram).
+const MAX_BATCH: usize = 64;
+// Send at most this many datagrams per broadcast interval, so a backlog drains
+// over a few intervals instead of being pinned at MAX_BATCH deltas per tick
+// (which would make the outbound buffer drop continuously under load).
+const MAX_BATCHES_PER_INTERVAL: usize = 16;
+// Ceiling on the outbound buffer. If the request rate outruns the broadcast
+// interval the oldest pending deltas are dropped (LWW on the receiver tolerates
+// the loss) rather than letting the buffer grow without bound.
+const MAX_OUTBOUND_DELTAS: usize = 4096;
+// Receive buffer for inbound datagrams.
+const RECV_BUF_BYTES: usize = 64 * 1024;
+// Bounds on the last-writer-wins table so it cannot leak like the unbounded
+// fairness map that chunk0-3 was filed to fix.
+const APPLIED_MAX_ENTRIES: u64 = 100_000;
+const APPLIED_TTL_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delta {
+    // Truncated request text -- only a prefix, never the full body.
+    prefix: String,
+    worker_url: String,
+    // Milliseconds since the Unix epoch; drives last-writer-wins.
+    timestamp: u64,
+    // Per-sender monotonic sequence number for dedup.
+    seq: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Batch {
+    node_id: String,
+    deltas: Vec<Delta>,
+}
+
+#[derive(Debug)]
+pub struct Gossip {
+    node_id: String,
+    peers: Vec<String>,
+    socket: UdpSocket,
+    outbound: Mutex<VecDeque<Delta>>,
+    seq: AtomicU64,
+    // Count of deltas dropped because the outbound buffer was full.
+    dropped: AtomicU64,
+}
+
+// Dedup and last-writer-wins bookkeeping for inbound deltas. Extracted from the
+// receive loop so the ordering/loss logic is unit-testable.
+struct DedupState {
+    // Highest sequence number seen per sender, to drop duplicates while
+    // tolerating UDP reordering and loss.
+    last_seq: HashMap<String, u64>,
+    // Last applied timestamp per (prefix, worker_url). Keyed by worker as well as
+    // prefix so a newer insert for the same prefix on a different worker is not
+    // dropped. Bounded by size + idle TTL.
+    applied: Cache<(String, String), u64>,
+}
+
+impl DedupState {
+    fn new() -> Self {
+        DedupState {
+            last_seq: HashMap::new(),
+            applied: Cache::builder()
+                .max_capacity(APPLIED_MAX_ENTRIES)
+                .time_to_idle(Duration::from_secs(APPLIED_TTL_SECS))
+                .build(),
+        }
+    }
+
+    // Whether `delta` from `node_id` should be applied to the local tree. Updates
+    // the dedup/LWW state as a side effect.
+    fn should_apply(&mut self, node_id: &str, delta: &Delta) -> bool {
+        if let Some(&seen) = self.last_seq.get(node_id) {
+            if delta.seq <= seen {
+                return false;
+            }
+        }
+        self.last_seq.insert(node_id.to_string(), delta.seq);
+
+        let key = (delta.prefix.clone(), delta.worker_url.clone());
+        if let Some(prev) = self.applied.get(&key) {
+            if delta.timestamp <= prev {
+                return false;
+            }
+        }
+        self.applied.insert(key, delta.timestamp);
+        true
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Gossip {
+    // Bind the gossip socket and start the broadcaster and receiver tasks.
+    pub fn start(
+        node_id: String,
+        bind_addr: &str,
+        peers: Vec<String>,
+        interval_secs: u64,
+        tree: Arc<Mutex<Tree>>,
+        max_tree_size: usize,
+    ) -> std::io::Result<Arc<Gossip>> {
+        let socket = UdpSocket::bind(bind_addr)?;
+
+        let gossip = Arc::new(Gossip {
+            node_id,
+            peers,
+            socket,
+            outbound: Mutex::new(VecDeque::new()),
+            seq: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+
+        gossip.spawn_broadcaster(interval_secs);
+        gossip.spawn_receiver(tree, max_tree_size);
+
+        Ok(gossip)
+    }
+
+    // Record a local insert for later broadcast. The text is truncated to a
+    // prefix so full request bodies never leave the process.
+    pub fn enqueue(&self, text: &str, worker_url: &str) {
+        let prefix: String = text.chars().take(MAX_PREFIX_CHARS).collect();
+        let delta = Delta {
+            prefix,
+            worker_url: worker_url.to_string(),
+            timestamp: now_millis(),
+            seq: self.seq.fetch_add(1, Ordering::SeqCst),
+        };
+        let mut outbound = self.outbound.lock().unwrap();
+        // Drop the oldest pending delta once the buffer is full. Receivers apply
+        // last-writer-wins, so losing a stale delta is harmless; letting the
+        // buffer grow unbounded when requests outrun the broadcast interval is
+        // not.
+        if outbound.len() >= MAX_OUTBOUND_DELTAS {
+            outbound.pop_front();
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped % MAX_OUTBOUND_DELTAS as u64 == 0 {
+                eprintln!(
+                    "gossip: outbound buffer full, dropped {} deltas (request rate exceeds broadcast interval)",
+                    dropped
+                );
+            }
+        }
+        outbound.push_back(delta);
+    }
+
+    fn spawn_broadcaster(self: &Arc<Self>, interval_secs: u64) {
+        let gossip = Arc::clone(self);
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(interval_secs));
+
+            if gossip.peers.is_empty() {
+                gossip.outbound.lock().unwrap().clear();
+                continue;
+            }
+
+            // Drain up to a bounded backlog worth of pending deltas.
+            let deltas: Vec<Delta> = {
+                let mut outbound = gossip.outbound.lock().unwrap();
+                let take = outbound.len().min(MAX_BATCH * MAX_BATCHES_PER_INTERVAL);
+                outbound.drain(..take).collect()
+            };
+            if deltas.is_empty() {
+                continue;
+            }
+
+            // Split into datagram-sized batches and send each to every peer.
+            for chunk in deltas.chunks(MAX_BATCH) {
+                let batch = Batch {
+                    node_id: gossip.node_id.clone(),
+                    deltas: chunk.to_vec(),
+                };
+                if let Ok(bytes) = serde_json::to_vec(&batch) {
+                    for peer in &gossip.peers {
+                        let _ = gossip.socket.send_to(&bytes, peer);
+                    }
+                }
+            }
+        });
+    }
+
+    fn spawn_receiver(self: &Arc<Self>, tree: Arc<Mutex<Tree>>, max_tree_size: usize) {
+        let gossip = Arc::clone(self);
+        thread::spawn(move || {
+            let mut buf = vec![0u8; RECV_BUF_BYTES];
+            let mut dedup = DedupState::new();
+
+            loop {
+                let size = match gossip.socket.recv_from(&mut buf) {
+                    Ok((size, _from)) => size,
+                    Err(_) => continue,
+                };
+
+                let batch: Batch = match serde_json::from_slice(&buf[..size]) {
+                    Ok(batch) => batch,
+                    Err(_) => continue,
+                };
+
+                // Ignore our own gossip looped back through a peer.
+                if batch.node_id == gossip.node_id {
+                    continue;
+                }
+
+                for delta in batch.deltas {
+                    if !dedup.should_apply(&batch.node_id, &delta) {
+                        continue;
+                    }
+
+                    let mut tree = tree.lock().unwrap();
+                    tree.insert(&delta.prefix, &delta.worker_url);
+                    tree.evict_tenant_data(max_tree_size);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(prefix: &str, worker: &str, timestamp: u64, seq: u64) -> Delta {
+        Delta {
+            prefix: prefix.to_string(),
+            worker_url: worker.to_string(),
+            timestamp,
+            seq,
+        }
+    }
+
+    #[test]
+    fn first_delta_is_applied_even_at_seq_zero() {
+        let mut state = DedupState::new();
+        assert!(state.should_apply("node-a", &delta("hello", "w1", 1, 0)));
+    }
+
+    #[test]
+    fn duplicate_and_reordered_sequence_numbers_are_dropped() {
+        let mut state = DedupState::new();
+        assert!(state.should_apply("node-a", &delta("a", "w1", 1, 5)));
+        // Same seq again -> duplicate.
+        assert!(!state.should_apply("node-a", &delta("a", "w1", 2, 5)));
+        // Older seq -> reordered/stale.
+        assert!(!state.should_apply("node-a", &delta("a", "w1", 3, 4)));
+        // Newer seq -> accepted.
+        assert!(state.should_apply("node-a", &delta("b", "w1", 4, 6)));
+    }
+
+    #[test]
+    fn sequence_numbers_are_tracked_per_sender() {
+        let mut state = DedupState::new();
+        assert!(state.should_apply("node-a", &delta("a", "w1", 1, 3)));
+        // A different sender with a low seq is still accepted.
+        assert!(state.should_apply("node-b", &delta("a", "w2", 1, 0)));
+    }
+
+    #[test]
+    fn last_writer_wins_on_timestamp_per_prefix_and_worker() {
+        let mut state = DedupState::new();
+        assert!(state.should_apply("node-a", &delta("p", "w1", 10, 0)));
+        // Older timestamp for the same (prefix, worker) loses.
+        assert!(!state.should_apply("node-a", &delta("p", "w1", 5, 1)));
+        // Newer timestamp wins.
+        assert!(state.should_apply("node-a", &delta("p", "w1", 20, 2)));
+        // Same prefix on a different worker is tracked independently.
+        assert!(state.should_apply("node-a", &delta("p", "w2", 15, 3)));
+    }
+}
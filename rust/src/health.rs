@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/*
+Worker health tracking with active probing and passive circuit-breaking.
+
+A single `WorkerHealth` is shared (behind `Arc`) across every routing strategy.
+Two signals feed it:
+
+  * active  -- a background task periodically GETs `/health` on each worker and
+               flips its availability flag;
+  * passive -- `dispatch` reports connection errors; after `failure_threshold`
+               consecutive errors the worker's circuit trips and it is skipped
+               until a probe (or a later success) closes it again.
+
+All three strategies filter their candidate set through `healthy_workers`, so an
+unavailable backend receives no traffic until it recovers.
+*/
+#[derive(Debug)]
+pub struct WorkerHealth {
+    states: Mutex<HashMap<String, WorkerState>>,
+    failure_threshold: u32,
+}
+
+#[derive(Debug)]
+struct WorkerState {
+    healthy: bool,
+    consecutive_failures: u32,
+}
+
+impl Default for WorkerState {
+    fn default() -> Self {
+        WorkerState {
+            healthy: true,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+impl WorkerHealth {
+    pub fn new(worker_urls: &[String], failure_threshold: u32) -> Self {
+        let mut states = HashMap::new();
+        for url in worker_urls {
+            states.insert(url.clone(), WorkerState::default());
+        }
+        WorkerHealth {
+            states: Mutex::new(states),
+            failure_threshold,
+        }
+    }
+
+    pub fn is_healthy(&self, worker_url: &str) -> bool {
+        self.states
+            .lock()
+            .unwrap()
+            .get(worker_url)
+            .map(|s| s.healthy)
+            .unwrap_or(true)
+    }
+
+    // Retain only currently-healthy workers, preserving the caller's order. If
+    // every worker is down we return an empty vec and the caller decides how to
+    // degrade.
+    pub fn healthy_workers(&self, worker_urls: &[String]) -> Vec<String> {
+        let states = self.states.lock().unwrap();
+        worker_urls
+            .iter()
+            .filter(|url| states.get(*url).map(|s| s.healthy).unwrap_or(true))
+            .cloned()
+            .collect()
+    }
+
+    // Passive signal: a successful dispatch closes the circuit.
+    pub fn record_success(&self, worker_url: &str) {
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(worker_url.to_string()).or_default();
+        state.consecutive_failures = 0;
+        state.healthy = true;
+    }
+
+    // Passive signal: trip the circuit after `failure_threshold` consecutive
+    // connection errors.
+    pub fn record_failure(&self, worker_url: &str) {
+        let mut states = self.states.lock().unwrap();
+        let threshold = self.failure_threshold;
+        let state = states.entry(worker_url.to_string()).or_default();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= threshold {
+            state.healthy = false;
+        }
+    }
+
+    // Active signal: result of a `/health` probe.
+    fn set_healthy(&self, worker_url: &str, healthy: bool) {
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(worker_url.to_string()).or_default();
+        state.healthy = healthy;
+        if healthy {
+            state.consecutive_failures = 0;
+        }
+    }
+
+    // Probe every worker's `/health` endpoint once.
+    async fn probe_once(&self, client: &reqwest::Client, worker_urls: &[String]) {
+        for url in worker_urls {
+            let healthy = matches!(
+                client.get(format!("{}/health", url)).send().await,
+                Ok(res) if res.status().is_success()
+            );
+            self.set_healthy(url, healthy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circuit_trips_after_threshold_consecutive_failures() {
+        let health = WorkerHealth::new(&["w1".to_string()], 3);
+        assert!(health.is_healthy("w1"));
+
+        health.record_failure("w1");
+        health.record_failure("w1");
+        // Below the threshold the worker stays healthy.
+        assert!(health.is_healthy("w1"));
+
+        health.record_failure("w1");
+        // Third consecutive failure trips the circuit.
+        assert!(!health.is_healthy("w1"));
+    }
+
+    #[test]
+    fn success_resets_the_failure_streak() {
+        let health = WorkerHealth::new(&["w1".to_string()], 2);
+        health.record_failure("w1");
+        // A success clears the streak so the next failure does not trip.
+        health.record_success("w1");
+        health.record_failure("w1");
+        assert!(health.is_healthy("w1"));
+        // And a second consecutive failure now trips.
+        health.record_failure("w1");
+        assert!(!health.is_healthy("w1"));
+    }
+
+    #[test]
+    fn success_recovers_a_tripped_worker() {
+        let health = WorkerHealth::new(&["w1".to_string()], 1);
+        health.record_failure("w1");
+        assert!(!health.is_healthy("w1"));
+        health.record_success("w1");
+        assert!(health.is_healthy("w1"));
+    }
+
+    #[test]
+    fn healthy_workers_filters_tripped_backends() {
+        let urls = vec!["w1".to_string(), "w2".to_string()];
+        let health = WorkerHealth::new(&urls, 1);
+        health.record_failure("w2");
+        assert_eq!(health.healthy_workers(&urls), vec!["w1".to_string()]);
+    }
+}
+
+// Spawn the background prober. Mirrors the eviction thread's std::thread pattern
+// but drives the async reqwest client on a dedicated current-thread runtime.
+pub fn spawn_prober(
+    health: std::sync::Arc<WorkerHealth>,
+    worker_urls: Vec<String>,
+    interval_secs: u64,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let client = reqwest::Client::new();
+        rt.block_on(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+                health.probe_once(&client, &worker_urls).await;
+            }
+        });
+    })
+}
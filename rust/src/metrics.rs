@@ -0,0 +1,163 @@
+use actix_web::{web, HttpResponse};
+use prometheus::{Encoder, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::sync::Arc;
+
+/*
+Prometheus metrics for the router.
+
+Every time series is labelled by `worker_url` so operators can watch load skew
+across backends from a single `/metrics` scrape instead of reading the eviction
+thread's `println!` output. A single `RouterMetrics` is shared (behind `Arc`)
+between `Router::new`, the eviction thread, and the `dispatch` hot path.
+*/
+#[derive(Debug)]
+pub struct RouterMetrics {
+    registry: Registry,
+    // Current in-flight requests per worker (mirrors `running_queue`).
+    running_requests: IntGaugeVec,
+    // Cumulative requests routed to each worker (mirrors `processed_queue`).
+    processed_total: IntCounterVec,
+    // Approximate radix-tree node count per worker.
+    tree_nodes: IntGaugeVec,
+    // Routing-decision breakdown, so the strategy mix is observable.
+    cache_hit_routes: IntCounterVec,
+    shortest_queue_routes: IntCounterVec,
+    fairness_fallbacks: IntCounterVec,
+}
+
+impl RouterMetrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let running_requests = IntGaugeVec::new(
+            Opts::new(
+                "sgl_router_running_requests",
+                "In-flight requests currently dispatched to each worker",
+            ),
+            &["worker_url"],
+        )
+        .unwrap();
+        let processed_total = IntCounterVec::new(
+            Opts::new(
+                "sgl_router_processed_requests_total",
+                "Total requests routed to each worker",
+            ),
+            &["worker_url"],
+        )
+        .unwrap();
+        let tree_nodes = IntGaugeVec::new(
+            Opts::new(
+                "sgl_router_tree_nodes",
+                "Approximate radix-tree node count per worker",
+            ),
+            &["worker_url"],
+        )
+        .unwrap();
+        let cache_hit_routes = IntCounterVec::new(
+            Opts::new(
+                "sgl_router_cache_hit_routes_total",
+                "Requests routed by cache-aware prefix match",
+            ),
+            &["worker_url"],
+        )
+        .unwrap();
+        let shortest_queue_routes = IntCounterVec::new(
+            Opts::new(
+                "sgl_router_shortest_queue_routes_total",
+                "Requests routed by shortest-queue load balancing",
+            ),
+            &["worker_url"],
+        )
+        .unwrap();
+        let fairness_fallbacks = IntCounterVec::new(
+            Opts::new(
+                "sgl_router_fairness_fallbacks_total",
+                "Requests that fell back to the default worker under fairness",
+            ),
+            &["worker_url"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(running_requests.clone())).unwrap();
+        registry.register(Box::new(processed_total.clone())).unwrap();
+        registry.register(Box::new(tree_nodes.clone())).unwrap();
+        registry.register(Box::new(cache_hit_routes.clone())).unwrap();
+        registry
+            .register(Box::new(shortest_queue_routes.clone()))
+            .unwrap();
+        registry.register(Box::new(fairness_fallbacks.clone())).unwrap();
+
+        RouterMetrics {
+            registry,
+            running_requests,
+            processed_total,
+            tree_nodes,
+            cache_hit_routes,
+            shortest_queue_routes,
+            fairness_fallbacks,
+        }
+    }
+
+    pub fn set_running(&self, worker_url: &str, count: i64) {
+        self.running_requests
+            .with_label_values(&[worker_url])
+            .set(count);
+    }
+
+    pub fn inc_processed(&self, worker_url: &str) {
+        self.processed_total.with_label_values(&[worker_url]).inc();
+    }
+
+    pub fn set_tree_nodes(&self, worker_url: &str, count: i64) {
+        self.tree_nodes.with_label_values(&[worker_url]).set(count);
+    }
+
+    pub fn record_cache_hit(&self, worker_url: &str) {
+        self.cache_hit_routes.with_label_values(&[worker_url]).inc();
+    }
+
+    pub fn record_shortest_queue(&self, worker_url: &str) {
+        self.shortest_queue_routes
+            .with_label_values(&[worker_url])
+            .inc();
+    }
+
+    pub fn record_fairness_fallback(&self, worker_url: &str) {
+        self.fairness_fallbacks
+            .with_label_values(&[worker_url])
+            .inc();
+    }
+
+    // Encode the registry into the Prometheus text exposition format.
+    pub fn gather(&self) -> String {
+        let mut buffer = Vec::new();
+        let encoder = TextEncoder::new();
+        let metric_families = self.registry.gather();
+        encoder.encode(&metric_families, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl Default for RouterMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Actix handler backing the `/metrics` endpoint.
+async fn metrics_handler(metrics: web::Data<RouterMetrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.gather())
+}
+
+// Register the `/metrics` endpoint on an actix `App`. The server wires this in
+// via `App::configure(metrics::configure(router.metrics().unwrap()))`, which
+// stores the shared registry in `app_data` and routes GET `/metrics` to the
+// handler above.
+pub fn configure(metrics: Arc<RouterMetrics>) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg: &mut web::ServiceConfig| {
+        cfg.app_data(web::Data::from(metrics))
+            .service(web::resource("/metrics").route(web::get().to(metrics_handler)));
+    }
+}
@@ -0,0 +1,76 @@
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use std::cell::RefCell;
+
+/*
+Per-thread fast RNG for the routing hot path.
+
+A xoshiro256++ generator is seeded once per worker thread from the OS RNG and
+kept in thread-local storage, so the power-of-two-choices sampling, the `Random`
+policy, and the cache-routing coin flip never take a global RNG lock.
+*/
+thread_local! {
+    static FAST_RNG: RefCell<Xoshiro256PlusPlus> =
+        RefCell::new(Xoshiro256PlusPlus::from_rng(rand::thread_rng()).unwrap());
+}
+
+fn with_fast_rng<T>(f: impl FnOnce(&mut Xoshiro256PlusPlus) -> T) -> T {
+    FAST_RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+// Random f32 in [0, 1), for probability checks.
+pub fn random_f32() -> f32 {
+    with_fast_rng(|rng| rng.gen::<f32>())
+}
+
+// Uniform index in [0, n).
+pub fn random_index(n: usize) -> usize {
+    with_fast_rng(|rng| rng.gen_range(0..n))
+}
+
+// Two distinct indices in [0, n) for power-of-two-choices. With a single worker
+// both indices collapse to 0.
+pub fn two_choices(n: usize) -> (usize, usize) {
+    if n <= 1 {
+        return (0, 0);
+    }
+    with_fast_rng(|rng| {
+        let a = rng.gen_range(0..n);
+        // Draw from the n-1 remaining slots and skip past `a` to stay uniform.
+        let mut b = rng.gen_range(0..n - 1);
+        if b >= a {
+            b += 1;
+        }
+        (a, b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_worker_collapses_to_zero() {
+        assert_eq!(two_choices(1), (0, 0));
+        assert_eq!(two_choices(0), (0, 0));
+    }
+
+    #[test]
+    fn two_choices_are_distinct_and_in_range() {
+        for n in 2..=8 {
+            for _ in 0..1000 {
+                let (a, b) = two_choices(n);
+                assert!(a < n, "index {} out of range for n={}", a, n);
+                assert!(b < n, "index {} out of range for n={}", b, n);
+                assert_ne!(a, b, "indices must be distinct for n={}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn random_index_stays_in_range() {
+        for _ in 0..1000 {
+            assert!(random_index(4) < 4);
+        }
+    }
+}
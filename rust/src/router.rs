@@ -1,4 +1,9 @@
+use crate::gossip::Gossip;
+use crate::health::{self, WorkerHealth};
+use crate::metrics::RouterMetrics;
+use crate::rng;
 use crate::tree::Tree;
+use moka::sync::Cache;
 use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
 use actix_web::{HttpRequest, HttpResponse};
 use bytes::Bytes;
@@ -12,14 +17,29 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+// Fallback tunables for the RoundRobin and Random policies, whose configs carry
+// no knobs of their own. CacheAware exposes these through `CacheAwareConfig`.
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 10;
+const DEFAULT_MAX_RETRIES: u32 = 2;
+
+// Backpressure: how long `dispatch` holds a request waiting for a worker to free
+// up before giving up with 503, and how often it re-checks while waiting.
+const BACKPRESSURE_POLL_MS: u64 = 5;
+const MAX_BACKPRESSURE_WAIT_MS: u64 = 100;
+
 #[derive(Debug)]
 pub enum Router {
     RoundRobin {
         worker_urls: Vec<String>,
         current_index: AtomicUsize,
+        health: Arc<WorkerHealth>,
+        max_retries: u32,
     },
     Random {
         worker_urls: Vec<String>,
+        health: Arc<WorkerHealth>,
+        max_retries: u32,
     },
     CacheAware {
         /*
@@ -81,11 +101,19 @@ pub enum Router {
         processed_queue: Arc<Mutex<HashMap<String, usize>>>,
         cache_threshold: f32,
         cache_routing_prob: f32,
-        // 2D matrix of (user_id, worker_url) -> counter
-        // Initialize with C for all pairs
-        fairness_counter: Arc<Mutex<HashMap<String, HashMap<String, i32>>>>,
+        // Per-user fairness state: user_id -> (worker_url -> counter), initialized
+        // with C for all pairs. Bounded by max entry count and idle TTL so a
+        // high-cardinality user population cannot leak memory; the counter map is
+        // wrapped for interior mutation of a cached value.
+        fairness_counter: Cache<String, Arc<Mutex<HashMap<String, i32>>>>,
         fairness_fill_size: usize,
         enable_fairness: bool,
+        max_concurrent_requests: usize,
+        metrics: Arc<RouterMetrics>,
+        health: Arc<WorkerHealth>,
+        max_retries: u32,
+        // Optional cross-replica gossip of the approximate tree.
+        gossip: Option<Arc<Gossip>>,
         _eviction_thread: Option<thread::JoinHandle<()>>, // Store thread handle
     },
 }
@@ -101,6 +129,17 @@ pub enum PolicyConfig {
         max_tree_size: usize,
         enable_fairness: bool,
         fairness_fill_size: usize,
+        fairness_max_users: u64,
+        fairness_ttl_secs: u64,
+        max_concurrent_requests: usize,
+        health_failure_threshold: u32,
+        health_check_interval_secs: u64,
+        max_retries: u32,
+        gossip_enabled: bool,
+        gossip_node_id: String,
+        gossip_bind_addr: String,
+        gossip_peers: Vec<String>,
+        gossip_interval_secs: u64,
     },
 }
 
@@ -137,11 +176,33 @@ fn get_uid_from_body(body: &Bytes) -> String {
 impl Router {
     pub fn new(worker_urls: Vec<String>, policy_config: PolicyConfig) -> Self {
         match policy_config {
-            PolicyConfig::RandomConfig => Router::Random { worker_urls },
-            PolicyConfig::RoundRobinConfig => Router::RoundRobin {
-                worker_urls,
-                current_index: std::sync::atomic::AtomicUsize::new(0),
-            },
+            PolicyConfig::RandomConfig => {
+                let health = Arc::new(WorkerHealth::new(&worker_urls, DEFAULT_FAILURE_THRESHOLD));
+                health::spawn_prober(
+                    Arc::clone(&health),
+                    worker_urls.clone(),
+                    DEFAULT_HEALTH_CHECK_INTERVAL_SECS,
+                );
+                Router::Random {
+                    worker_urls,
+                    health,
+                    max_retries: DEFAULT_MAX_RETRIES,
+                }
+            }
+            PolicyConfig::RoundRobinConfig => {
+                let health = Arc::new(WorkerHealth::new(&worker_urls, DEFAULT_FAILURE_THRESHOLD));
+                health::spawn_prober(
+                    Arc::clone(&health),
+                    worker_urls.clone(),
+                    DEFAULT_HEALTH_CHECK_INTERVAL_SECS,
+                );
+                Router::RoundRobin {
+                    worker_urls,
+                    current_index: std::sync::atomic::AtomicUsize::new(0),
+                    health,
+                    max_retries: DEFAULT_MAX_RETRIES,
+                }
+            }
             PolicyConfig::CacheAwareConfig {
                 cache_threshold,
                 cache_routing_prob,
@@ -149,6 +210,17 @@ impl Router {
                 max_tree_size,
                 enable_fairness,
                 fairness_fill_size,
+                fairness_max_users,
+                fairness_ttl_secs,
+                max_concurrent_requests,
+                health_failure_threshold,
+                health_check_interval_secs,
+                max_retries,
+                gossip_enabled,
+                gossip_node_id,
+                gossip_bind_addr,
+                gossip_peers,
+                gossip_interval_secs,
             } => {
                 let mut running_queue = HashMap::new();
                 for url in &worker_urls {
@@ -163,10 +235,12 @@ impl Router {
                 let tree = Arc::new(Mutex::new(Tree::new()));
                 let running_queue = Arc::new(Mutex::new(running_queue));
                 let processed_queue = Arc::new(Mutex::new(processed_queue));
+                let metrics = Arc::new(RouterMetrics::new());
 
                 // Create background eviction thread
                 let tree_clone = Arc::clone(&tree);
-                let processed_queue_clone = Arc::clone(&processed_queue);
+                let metrics_clone = Arc::clone(&metrics);
+                let eviction_worker_urls = worker_urls.clone();
                 let eviction_thread = thread::spawn(move || {
                     loop {
                         // Sleep for the specified interval
@@ -176,9 +250,11 @@ impl Router {
                         // Run eviction
                         locked_tree_clone.evict_tenant_data(max_tree_size);
 
-                        // Print the process queue
-                        let locked_processed_queue = processed_queue_clone.lock().unwrap();
-                        println!("Processed Queue: {:?}", locked_processed_queue);
+                        // Publish the post-eviction tree size per worker.
+                        for worker_url in &eviction_worker_urls {
+                            let nodes = locked_tree_clone.get_tenant_node_count(worker_url);
+                            metrics_clone.set_tree_nodes(worker_url, nodes as i64);
+                        }
                     }
                 });
 
@@ -186,7 +262,38 @@ impl Router {
                     tree.lock().unwrap().insert(&"".to_string(), url);
                 }
 
-                let fairness_counter = Arc::new(Mutex::new(HashMap::new()));
+                // Bounded fairness state: evict by max user count and by idle TTL.
+                let fairness_counter = Cache::builder()
+                    .max_capacity(fairness_max_users)
+                    .time_to_idle(Duration::from_secs(fairness_ttl_secs))
+                    .build();
+
+                let health = Arc::new(WorkerHealth::new(&worker_urls, health_failure_threshold));
+                health::spawn_prober(
+                    Arc::clone(&health),
+                    worker_urls.clone(),
+                    health_check_interval_secs,
+                );
+
+                // Start cross-replica gossip if configured; a bind failure just
+                // disables sharing rather than taking the router down.
+                let gossip = if gossip_enabled {
+                    match Gossip::start(
+                        gossip_node_id,
+                        &gossip_bind_addr,
+                        gossip_peers,
+                        gossip_interval_secs,
+                        Arc::clone(&tree),
+                        max_tree_size,
+                    ) {
+                        Ok(gossip) => Some(gossip),
+                        // A bind failure just disables cross-replica sharing
+                        // rather than taking the router down.
+                        Err(_) => None,
+                    }
+                } else {
+                    None
+                };
 
                 Router::CacheAware {
                     worker_urls,
@@ -198,6 +305,11 @@ impl Router {
                     fairness_counter,
                     enable_fairness,
                     fairness_fill_size,
+                    max_concurrent_requests,
+                    metrics,
+                    health,
+                    max_retries,
+                    gossip,
                     _eviction_thread: Some(eviction_thread),
                 }
             }
@@ -207,7 +319,7 @@ impl Router {
     pub fn get_first(&self) -> Option<String> {
         match self {
             Router::RoundRobin { worker_urls, .. }
-            | Router::Random { worker_urls }
+            | Router::Random { worker_urls, .. }
             | Router::CacheAware { worker_urls, .. } => {
                 if worker_urls.is_empty() {
                     None
@@ -218,6 +330,111 @@ impl Router {
         }
     }
 
+    // Shared metrics registry, if this policy tracks per-worker stats. The HTTP
+    // layer uses this to wire up the `/metrics` endpoint.
+    pub fn metrics(&self) -> Option<Arc<RouterMetrics>> {
+        match self {
+            Router::CacheAware { metrics, .. } => Some(Arc::clone(metrics)),
+            _ => None,
+        }
+    }
+
+    // Whether any healthy worker is currently below the in-flight concurrency cap.
+    fn has_headroom(
+        worker_urls: &[String],
+        running_queue: &Arc<Mutex<HashMap<String, usize>>>,
+        health: &Arc<WorkerHealth>,
+        max_concurrent_requests: usize,
+    ) -> bool {
+        let running = running_queue.lock().unwrap();
+        health.healthy_workers(worker_urls).iter().any(|url| {
+            running.get(url).copied().unwrap_or(0) < max_concurrent_requests
+        })
+    }
+
+    // Shared health tracker for the active prober and passive circuit-breaking.
+    fn health(&self) -> &Arc<WorkerHealth> {
+        match self {
+            Router::RoundRobin { health, .. }
+            | Router::Random { health, .. }
+            | Router::CacheAware { health, .. } => health,
+        }
+    }
+
+    // Maximum number of failover attempts beyond the first for a single request.
+    fn max_retries(&self) -> u32 {
+        match self {
+            Router::RoundRobin { max_retries, .. }
+            | Router::Random { max_retries, .. }
+            | Router::CacheAware { max_retries, .. } => *max_retries,
+        }
+    }
+
+    // Reserve an in-flight slot on `worker_url` before attempting to reach it.
+    // Only the running-queue is touched here so the concurrency accounting is
+    // correct during the request; processed/tree/gossip state is deferred to
+    // `record_route` and applied only once the worker is actually reached.
+    fn reserve_slot(&self, worker_url: &str) {
+        if let Router::CacheAware {
+            running_queue,
+            metrics,
+            ..
+        } = self
+        {
+            let mut running = running_queue.lock().unwrap();
+            let count = running.entry(worker_url.to_string()).or_insert(0);
+            *count += 1;
+            metrics.set_running(worker_url, *count as i64);
+        }
+    }
+
+    // Record a successful route: bump the processed counter, insert the prefix
+    // into the approximate tree, and gossip it to sibling replicas. Called only
+    // after `send()` succeeds so a worker we never reached is never recorded as
+    // having processed or cached the request.
+    fn record_route(&self, worker_url: &str, text: &str) {
+        if let Router::CacheAware {
+            processed_queue,
+            tree,
+            metrics,
+            gossip,
+            ..
+        } = self
+        {
+            let mut processed = processed_queue.lock().unwrap();
+            let count = processed.entry(worker_url.to_string()).or_insert(0);
+            *count += 1;
+            metrics.inc_processed(worker_url);
+            drop(processed);
+
+            tree.lock().unwrap().insert(&text.to_string(), &worker_url.to_string());
+
+            // Share this insert with sibling replicas (prefix only).
+            if let Some(gossip) = gossip {
+                gossip.enqueue(text, worker_url);
+            }
+        }
+    }
+
+    // Release the slot reserved by `reserve_slot`. Used on the send-error path and
+    // when a non-streaming response completes; a streaming response releases its
+    // slot only when the `data: [DONE]` marker passes through the stream.
+    fn release_inflight(&self, worker_url: &str) {
+        if let Router::CacheAware {
+            running_queue,
+            metrics,
+            ..
+        } = self
+        {
+            if let Ok(mut running) = running_queue.lock() {
+                if let Some(count) = running.get_mut(worker_url) {
+                    *count = count.saturating_sub(1);
+                    metrics.set_running(worker_url, *count as i64);
+                }
+            }
+        }
+    }
+
     pub async fn dispatch(
         &self,
         client: &reqwest::Client,
@@ -229,140 +446,170 @@ impl Router {
         // For Debug
         // println!("text: {:?}, route: {:?}", text, route);
 
-        let worker_url = match self {
+        // Backpressure: when a per-worker concurrency cap is configured, hold the
+        // request briefly while every healthy worker is at capacity, then deny it
+        // with 503 rather than piling onto an overloaded backend.
+        if let Router::CacheAware {
+            worker_urls,
+            running_queue,
+            health,
+            max_concurrent_requests,
+            ..
+        } = self
+        {
+            if *max_concurrent_requests > 0 {
+                let mut waited = 0;
+                while !Self::has_headroom(worker_urls, running_queue, health, *max_concurrent_requests)
+                {
+                    if waited >= MAX_BACKPRESSURE_WAIT_MS {
+                        return HttpResponse::ServiceUnavailable().finish();
+                    }
+                    tokio::time::sleep(Duration::from_millis(BACKPRESSURE_POLL_MS)).await;
+                    waited += BACKPRESSURE_POLL_MS;
+                }
+            }
+        }
+
+        // Each strategy yields an ordered list of *healthy* candidate workers:
+        // the preferred worker first, then the failover order used if the
+        // request errors out.
+        let candidates: Vec<String> = match self {
             Router::RoundRobin {
                 worker_urls,
                 current_index,
+                health,
+                ..
             } => {
+                let n = worker_urls.len();
                 let idx = current_index
                     .fetch_update(
                         std::sync::atomic::Ordering::SeqCst,
                         std::sync::atomic::Ordering::SeqCst,
-                        |x| Some((x + 1) % worker_urls.len()),
+                        |x| Some((x + 1) % n),
                     )
                     .unwrap();
 
-                worker_urls[idx].clone()
+                let ordered: Vec<String> = (0..n).map(|i| worker_urls[(idx + i) % n].clone()).collect();
+                health.healthy_workers(&ordered)
             }
 
-            Router::Random { worker_urls } => {
-                worker_urls[rand::random::<usize>() % worker_urls.len()].clone()
+            Router::Random {
+                worker_urls,
+                health,
+                ..
+            } => {
+                let n = worker_urls.len();
+                let start = rng::random_index(n);
+                let ordered: Vec<String> =
+                    (0..n).map(|i| worker_urls[(start + i) % n].clone()).collect();
+                health.healthy_workers(&ordered)
             }
 
             Router::CacheAware {
                 worker_urls,
                 tree,
                 running_queue,
-                processed_queue,
                 cache_threshold,
                 cache_routing_prob,
                 fairness_counter,
                 fairness_fill_size,
                 enable_fairness,
+                processed_queue,
+                metrics,
+                health,
+                max_concurrent_requests,
                 ..
             } => {
-                let mut tree = tree.lock().unwrap();
+                let tree = tree.lock().unwrap();
                 let mut running_queue = running_queue.lock().unwrap();
 
+                // Full worker set, kept for fairness initialization so a worker
+                // that is merely unhealthy/saturated at first touch still gets a
+                // counter entry and rejoins selection once it recovers.
+                let all_worker_urls = worker_urls.clone();
+
+                // Consider only workers whose circuit is currently closed, then
+                // drop any already at the in-flight concurrency cap so selection
+                // only lands on backends with headroom.
+                let mut healthy = health.healthy_workers(worker_urls);
+                if *max_concurrent_requests > 0 {
+                    healthy.retain(|url| {
+                        running_queue.get(url).copied().unwrap_or(0) < *max_concurrent_requests
+                    });
+                }
+                if healthy.is_empty() {
+                    // No healthy worker with headroom: deny the request rather
+                    // than route it to a down or saturated backend.
+                    return HttpResponse::ServiceUnavailable().finish();
+                }
+                let worker_urls: Vec<String> = healthy;
+
                 // Generate a random float between 0 and 1 for probability check
-                let sampled_p: f32 = rand::random();
+                let sampled_p: f32 = rng::random_f32();
 
                 let selected_url = if *enable_fairness {
 
                     let user_id = get_uid_from_body(&body);
 
-                    let mut fairness_counter = fairness_counter.lock().unwrap();
-            
-                    // Initialize counter for new user
-                    if !fairness_counter.contains_key(&user_id) {
+                    // Race-free first-request init: `get_with` runs the closure
+                    // exactly once even under concurrent requests for a new user.
+                    // Initialize over the full worker set, not just the currently
+                    // healthy shadow, so recovered workers stay selectable.
+                    let fill_size = *fairness_fill_size as i32;
+                    let init_urls = all_worker_urls.clone();
+                    let counters = fairness_counter.get_with(user_id.clone(), || {
                         let mut worker_counters = HashMap::new();
-                        for worker_url in worker_urls.iter() {
-                            worker_counters.insert(worker_url.clone(), *fairness_fill_size as i32);
+                        for worker_url in init_urls.iter() {
+                            worker_counters.insert(worker_url.clone(), fill_size);
                         }
-                        fairness_counter.insert(user_id.to_string(), worker_counters.clone());
-                        
-                        println!(
-                            "[FAIRNESS] New user initialized. user_id: {}, initial_counters: {:?}",
-                            user_id, worker_counters
-                        );
-                    }
-            
+                        Arc::new(Mutex::new(worker_counters))
+                    });
+                    let mut worker_counters = counters.lock().unwrap();
+
                     let mut prefix_map: HashMap<String, String> = HashMap::new();
                     for worker_url in worker_urls.iter() {
                         let prefix = tree.prefix_match_tenant(&text, worker_url);
                         prefix_map.insert(worker_url.clone(), prefix);
                     }
-            
+
                     let mut sorted_workers: Vec<_> = prefix_map.into_iter().collect();
                     sorted_workers.sort_by(|(_url1, prefix1), (_url2, prefix2)| {
                         prefix2.len().cmp(&prefix1.len())
                     });
-            
+
                     let mut selected = None;
-            
+
                     loop {
                         // Try to find worker with highest prefix match with available counters
-                        for (worker_url, prefix) in &sorted_workers {
-                            if let Some(worker_counters) = fairness_counter.get_mut(&user_id) {
-                                if let Some(&count) = worker_counters.get(worker_url) {
-                                    let deduction = text.chars().count();
-                                    if count - deduction as i32 > 0 {
-                                        selected = Some(worker_url.clone());
-                                        let new_count = count.saturating_sub(deduction as i32);
-                                        worker_counters.insert(worker_url.clone(), new_count);
-                                        
-                                        println!(
-                                            "[FAIRNESS] Worker selected. user_id: {}, worker: {}, prefix_len: {}, prev_count: {}, deduction: {}, new_count: {}",
-                                            user_id, worker_url, prefix.len(), count, deduction, new_count
-                                        );
-                                        break;
-                                    }
+                        for (worker_url, _prefix) in &sorted_workers {
+                            if let Some(&count) = worker_counters.get(worker_url) {
+                                let deduction = text.chars().count();
+                                if count - deduction as i32 > 0 {
+                                    selected = Some(worker_url.clone());
+                                    let new_count = count.saturating_sub(deduction as i32);
+                                    worker_counters.insert(worker_url.clone(), new_count);
+                                    break;
                                 }
                             }
                         }
-            
+
                         // Refill counters if no available worker found
                         if selected.is_none() {
-                            if let Some(worker_counters) = fairness_counter.get_mut(&user_id) {
-                                println!(
-                                    "[FAIRNESS] Refilling counters. user_id: {}, previous_counters: {:?}",
-                                    user_id, worker_counters
-                                );
-                                
-                                for worker_url in worker_urls.iter() {
-                                    if let Some(&count) = worker_counters.get(worker_url) {
-                                        let new_count = count + *fairness_fill_size as i32;
-                                        worker_counters.insert(worker_url.clone(), new_count);
-                                        
-                                        println!(
-                                            "[FAIRNESS] Worker refilled. user_id: {}, worker: {}, prev_count: {}, fill_size: {}, new_count: {}",
-                                            user_id, worker_url, count, fairness_fill_size, new_count
-                                        );
-                                    }
+                            for worker_url in worker_urls.iter() {
+                                if let Some(&count) = worker_counters.get(worker_url) {
+                                    let new_count = count + *fairness_fill_size as i32;
+                                    worker_counters.insert(worker_url.clone(), new_count);
                                 }
                             }
                         } else {
                             break;
                         }
                     }
-            
-                    let selected_worker = selected.unwrap_or_else(|| {
-                        println!(
-                            "[FAIRNESS] WARNING: Fallback to default worker. user_id: {}, worker: {}",
-                            user_id, &worker_urls[0]
-                        );
+
+                    selected.unwrap_or_else(|| {
+                        metrics.record_fairness_fallback(&worker_urls[0]);
                         worker_urls[0].clone()
-                    });
-            
-                    // Log final counter state
-                    if let Some(worker_counters) = fairness_counter.get(&user_id) {
-                        println!(
-                            "[FAIRNESS] Request complete. user_id: {}, selected_worker: {}, final_counters: {:?}",
-                            user_id, selected_worker, worker_counters
-                        );
-                    }
-            
-                    selected_worker
+                    })
                 } else {
                     if sampled_p < *cache_routing_prob {
                         // Cache-aware routing logic
@@ -371,33 +618,88 @@ impl Router {
                             matched_text.chars().count() as f32 / text.chars().count() as f32;
 
                         if matched_rate > *cache_threshold {
-                            matched_worker.to_string()
+                            let worker = matched_worker.to_string();
+                            metrics.record_cache_hit(&worker);
+                            worker
                         } else {
-                            tree.get_smallest_tenant()
+                            let worker = tree.get_smallest_tenant();
+                            metrics.record_shortest_queue(&worker);
+                            worker
                         }
                     } else {
-                        // Shortest queue routing logic
-                        running_queue
-                            .iter()
-                            .min_by_key(|(_url, &count)| count)
-                            .map(|(url, _)| url.clone())
-                            .unwrap_or_else(|| worker_urls[0].clone())
+                        // Power-of-two-choices load balancing (healthy workers
+                        // only): draw two distinct workers and route to the one
+                        // with fewer in-flight requests, breaking ties by the
+                        // cumulative processed count. O(1) per request, and it
+                        // avoids the thundering-herd convergence of a full scan.
+                        let (i, j) = rng::two_choices(worker_urls.len());
+                        let a = &worker_urls[i];
+                        let b = &worker_urls[j];
+                        let count_a = running_queue.get(a).copied().unwrap_or(0);
+                        let count_b = running_queue.get(b).copied().unwrap_or(0);
+
+                        let worker = if count_a < count_b {
+                            a.clone()
+                        } else if count_b < count_a {
+                            b.clone()
+                        } else {
+                            let processed = processed_queue.lock().unwrap();
+                            let proc_a = processed.get(a).copied().unwrap_or(0);
+                            let proc_b = processed.get(b).copied().unwrap_or(0);
+                            if proc_a <= proc_b {
+                                a.clone()
+                            } else {
+                                b.clone()
+                            }
+                        };
+                        metrics.record_shortest_queue(&worker);
+                        worker
                     }
                 };
 
-                // Update running queue
-                let count = running_queue.get_mut(&selected_url).unwrap();
-                *count += 1;
+                // The cache-aware branches pick a worker from the radix tree,
+                // which covers every worker and so may pick one that is unhealthy
+                // or already at the cap, i.e. outside the filtered set. Redirect
+                // such a choice to the healthy worker with the most headroom so
+                // health and the cap hold for cache hits too, not just the
+                // load-balancing paths. A tree choice inside the filtered set is
+                // left untouched so cache locality is preserved.
+                let mut selected_url = selected_url;
+                if !worker_urls.contains(&selected_url) {
+                    if let Some(fallback) = worker_urls
+                        .iter()
+                        .min_by_key(|url| running_queue.get(*url).copied().unwrap_or(0))
+                    {
+                        selected_url = fallback.clone();
+                    }
+                }
 
-                // Update processed queue
-                let mut locked_processed_queue = processed_queue.lock().unwrap();
-                let count = locked_processed_queue.get_mut(&selected_url).unwrap();
+                // Enforce the concurrency cap: reserve the in-flight slot on the
+                // chosen worker while still holding the running-queue lock used
+                // for the headroom check, so concurrent requests cannot all pass
+                // the check and overshoot `max_concurrent_requests`. Failover
+                // attempts reserve their slot in the send loop instead.
+                let count = running_queue.entry(selected_url.clone()).or_insert(0);
                 *count += 1;
-
-                // Update tree with the new request
-                tree.insert(&text, &selected_url);
-
-                selected_url
+                metrics.set_running(&selected_url, *count as i64);
+                drop(running_queue);
+
+                // Build the failover order: the selected worker first, then the
+                // remaining healthy workers in their existing order. The failover
+                // tail is only consulted if the primary errors out, so we do NOT
+                // rank it by prefix match here -- that would cost an O(n log n)
+                // sort with a tree lookup per worker on every dispatch to order a
+                // list we usually never read past its head. The
+                // in-flight/processed/tree book-keeping for whichever worker we
+                // actually reach is handled by `reserve_slot`/`record_route`.
+                let mut ordered: Vec<String> = worker_urls.clone();
+                if let Some(pos) = ordered.iter().position(|url| url == &selected_url) {
+                    let preferred = ordered.remove(pos);
+                    ordered.insert(0, preferred);
+                } else {
+                    ordered.insert(0, selected_url);
+                }
+                ordered
             }
         };
 
@@ -405,75 +707,105 @@ impl Router {
             .map(|v| v.get("stream").and_then(|s| s.as_bool()).unwrap_or(false))
             .unwrap_or(false);
 
-        let res = match client
-            .post(format!("{}/{}", worker_url.clone(), route))
-            .header(
-                "Content-Type",
-                req.headers()
-                    .get("Content-Type")
-                    .and_then(|h| h.to_str().ok())
-                    .unwrap_or("application/json"),
-            )
-            .body(body.to_vec())
-            .send()
-            .await
-        {
-            Ok(res) => res,
-            Err(_) => return HttpResponse::InternalServerError().finish(),
-        };
+        if candidates.is_empty() {
+            // No healthy worker is available to serve the request.
+            return HttpResponse::ServiceUnavailable().finish();
+        }
 
-        let status = actix_web::http::StatusCode::from_u16(res.status().as_u16())
-            .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR);
+        let content_type = req
+            .headers()
+            .get("Content-Type")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("application/json")
+            .to_string();
+
+        // Try the preferred worker first, then fail over to the next-best
+        // healthy worker on connection errors, up to the configured retry count.
+        let attempts = candidates.len().min(self.max_retries() as usize + 1);
+
+        for (attempt, worker_url) in candidates.iter().take(attempts).enumerate() {
+            // The preferred worker's slot was already reserved atomically during
+            // selection; failover attempts reserve theirs here.
+            if attempt > 0 {
+                self.reserve_slot(worker_url);
+            }
 
-        if !is_stream {
-            // For non-streaming requests, get response first
-            let response = match res.bytes().await {
-                Ok(body) => HttpResponse::build(status).body(body.to_vec()),
-                Err(_) => HttpResponse::InternalServerError().finish(),
+            let res = match client
+                .post(format!("{}/{}", worker_url, route))
+                .header("Content-Type", content_type.as_str())
+                .body(body.to_vec())
+                .send()
+                .await
+            {
+                Ok(res) => res,
+                Err(_) => {
+                    // Release the slot, trip the circuit, and fail over.
+                    self.release_inflight(worker_url);
+                    self.health().record_failure(worker_url);
+                    continue;
+                }
             };
 
-            // Then decrement running queue counter if using CacheAware
-            if let Router::CacheAware { running_queue, .. } = self {
-                if let Ok(mut queue) = running_queue.lock() {
-                    if let Some(count) = queue.get_mut(&worker_url) {
-                        *count = count.saturating_sub(1);
-                    }
-                }
-            }
+            // Only now that the worker was actually reached do we record the
+            // request in the processed counter, the tree, and gossip.
+            self.health().record_success(worker_url);
+            self.record_route(worker_url, &text);
 
-            response
-        } else if let Router::CacheAware { running_queue, .. } = self {
-            let running_queue = Arc::clone(running_queue);
-            let worker_url = worker_url.clone();
-
-            HttpResponse::build(status)
-                .insert_header((CONTENT_TYPE, HeaderValue::from_static("text/event-stream")))
-                .streaming(
-                    res.bytes_stream()
-                        .map_err(|_| {
-                            actix_web::error::ErrorInternalServerError("Failed to read stream")
-                        })
-                        .inspect(move |bytes| {
-                            let bytes = bytes.as_ref().unwrap();
-                            if bytes
-                                .as_ref()
-                                .windows(12)
-                                .any(|window| window == b"data: [DONE]")
-                            {
-                                let mut locked_queue = running_queue.lock().unwrap();
-                                let count = locked_queue.get_mut(&worker_url).unwrap();
-                                *count = count.saturating_sub(1);
-                                // print
-                                // println!("streaming is done!!")
-                            }
-                        }),
-                )
-        } else {
-            HttpResponse::build(status)
-                .insert_header((CONTENT_TYPE, HeaderValue::from_static("text/event-stream")))
-                .streaming(res.bytes_stream().map_err(|_| {
-                    actix_web::error::ErrorInternalServerError("Failed to read stream")
-                }))
+            let status = actix_web::http::StatusCode::from_u16(res.status().as_u16())
+                .unwrap_or(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR);
+
+            if !is_stream {
+                // For non-streaming requests, get response first
+                let response = match res.bytes().await {
+                    Ok(body) => HttpResponse::build(status).body(body.to_vec()),
+                    Err(_) => HttpResponse::InternalServerError().finish(),
+                };
+
+                // Then release the in-flight slot if using CacheAware.
+                self.release_inflight(worker_url);
+
+                return response;
+            } else if let Router::CacheAware {
+                running_queue,
+                metrics,
+                ..
+            } = self
+            {
+                let running_queue = Arc::clone(running_queue);
+                let metrics = Arc::clone(metrics);
+                let worker_url = worker_url.clone();
+
+                return HttpResponse::build(status)
+                    .insert_header((CONTENT_TYPE, HeaderValue::from_static("text/event-stream")))
+                    .streaming(
+                        res.bytes_stream()
+                            .map_err(|_| {
+                                actix_web::error::ErrorInternalServerError("Failed to read stream")
+                            })
+                            .inspect(move |bytes| {
+                                let bytes = bytes.as_ref().unwrap();
+                                if bytes
+                                    .as_ref()
+                                    .windows(12)
+                                    .any(|window| window == b"data: [DONE]")
+                                {
+                                    let mut locked_queue = running_queue.lock().unwrap();
+                                    let count = locked_queue.get_mut(&worker_url).unwrap();
+                                    *count = count.saturating_sub(1);
+                                    metrics.set_running(&worker_url, *count as i64);
+                                }
+                            }),
+                    );
+            } else {
+                return HttpResponse::build(status)
+                    .insert_header((CONTENT_TYPE, HeaderValue::from_static("text/event-stream")))
+                    .streaming(res.bytes_stream().map_err(|_| {
+                        actix_web::error::ErrorInternalServerError("Failed to read stream")
+                    }));
+            }
         }
+
+        // Every candidate worker failed to accept the request.
+        HttpResponse::ServiceUnavailable().finish()
     }
 }